@@ -0,0 +1,141 @@
+//! Raw Ethernet frame capture and injection via `AF_PACKET` sockets.
+//!
+//! This gives access to the datalink layer directly, bypassing the IP stack entirely, which is
+//! the primitive needed to build tools such as ARP probing or a tcpdump-style sniffer.
+
+use std::ffi::CString;
+use std::io;
+use std::mem::size_of;
+use std::mem::MaybeUninit;
+
+/// The length of an Ethernet MAC address, in bytes.
+pub const MAC_LEN: usize = 6;
+
+/// A raw `AF_PACKET` socket bound to a single interface.
+pub struct RawSocket {
+	/// The socket's file descriptor.
+	fd: i32,
+	/// The index of the interface the socket is bound to.
+	ifindex: i32,
+}
+
+impl RawSocket {
+	/// Opens a raw socket on the interface named `ifname`, receiving frames of the given
+	/// `protocol` (an `ETH_P_*` constant, in host byte order).
+	pub fn new(ifname: &str, protocol: u16) -> io::Result<Self> {
+		let fd = unsafe {
+			libc::socket(
+				libc::AF_PACKET,
+				libc::SOCK_RAW,
+				protocol.to_be() as i32,
+			)
+		};
+		if fd < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		let ifindex = match Self::resolve_ifindex(ifname) {
+			Ok(i) => i,
+			Err(e) => {
+				unsafe {
+					libc::close(fd);
+				}
+				return Err(e);
+			}
+		};
+
+		let mut addr: libc::sockaddr_ll = unsafe { MaybeUninit::zeroed().assume_init() };
+		addr.sll_family = libc::AF_PACKET as _;
+		addr.sll_protocol = protocol.to_be();
+		addr.sll_ifindex = ifindex;
+
+		let res = unsafe {
+			libc::bind(
+				fd,
+				&addr as *const _ as *const libc::sockaddr,
+				size_of::<libc::sockaddr_ll>() as _,
+			)
+		};
+		if res < 0 {
+			let e = io::Error::last_os_error();
+			unsafe {
+				libc::close(fd);
+			}
+			return Err(e);
+		}
+
+		Ok(Self {
+			fd,
+			ifindex,
+		})
+	}
+
+	/// Resolves the interface index of `ifname`.
+	fn resolve_ifindex(ifname: &str) -> io::Result<i32> {
+		let ifname = CString::new(ifname)
+			.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+
+		let ifindex = unsafe { libc::if_nametoindex(ifname.as_ptr()) };
+		if ifindex == 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(ifindex as i32)
+	}
+
+	/// Sends `frame` on the interface, addressed to the link-layer destination `dest`.
+	pub fn send_to(&self, frame: &[u8], dest: [u8; MAC_LEN]) -> io::Result<usize> {
+		let mut addr: libc::sockaddr_ll = unsafe { MaybeUninit::zeroed().assume_init() };
+		addr.sll_family = libc::AF_PACKET as _;
+		addr.sll_ifindex = self.ifindex;
+		addr.sll_halen = MAC_LEN as _;
+		addr.sll_addr[..MAC_LEN].copy_from_slice(&dest);
+
+		let res = unsafe {
+			libc::sendto(
+				self.fd,
+				frame.as_ptr() as *const _,
+				frame.len(),
+				0,
+				&addr as *const _ as *const libc::sockaddr,
+				size_of::<libc::sockaddr_ll>() as _,
+			)
+		};
+		if res < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(res as usize)
+	}
+
+	/// Receives a frame into `buf`, returning its length along with the link-layer address it
+	/// was received from.
+	pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, libc::sockaddr_ll)> {
+		let mut addr: libc::sockaddr_ll = unsafe { MaybeUninit::zeroed().assume_init() };
+		let mut addr_len = size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+
+		let res = unsafe {
+			libc::recvfrom(
+				self.fd,
+				buf.as_mut_ptr() as *mut _,
+				buf.len(),
+				0,
+				&mut addr as *mut _ as *mut libc::sockaddr,
+				&mut addr_len,
+			)
+		};
+		if res < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok((res as usize, addr))
+	}
+}
+
+impl Drop for RawSocket {
+	fn drop(&mut self) {
+		unsafe {
+			libc::close(self.fd);
+		}
+	}
+}
@@ -2,35 +2,59 @@
 //!
 //! The netlink interface can be accessed from userspace through a socket.
 
+pub mod conntrack;
 pub mod route;
 pub mod util;
 
+use std::cell::Cell;
 use std::ffi::*;
 use std::io;
 use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ptr::null;
 
 /// Netlink family: route
 const NETLINK_ROUTE: c_int = 0;
 
+/// Alignment applied to netlink messages.
+const NLMSG_ALIGNTO: u32 = 4;
+
+/// Rounds `len` up to the next netlink message alignment boundary.
+fn nlmsg_align(len: u32) -> u32 {
+	(len + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
+}
+
 /// Netlink message header.
 #[repr(C)]
-struct NlMsgHdr {
+pub(crate) struct NlMsgHdr {
 	/// Length of the message including header
-	nlmsg_len: u32,
+	pub(crate) nlmsg_len: u32,
 	/// Type of message content
-	nlmsg_type: u16,
+	pub(crate) nlmsg_type: u16,
 	/// Additional flags
-	nlmsg_flags: u16,
+	pub(crate) nlmsg_flags: u16,
 	/// Sequence number
-	nlmsg_seq: u32,
+	pub(crate) nlmsg_seq: u32,
 	/// Sender port ID
-	nlmsg_pid: u32,
+	pub(crate) nlmsg_pid: u32,
+}
+
+/// Trait implemented by types that can be decoded from the payload of a netlink message (the
+/// bytes following the [`NlMsgHdr`]).
+pub trait NlPayload: Sized {
+	/// Decodes `self` from `payload`.
+	fn parse(payload: &[u8]) -> io::Result<Self>;
 }
 
 /// A netlink socket.
 pub struct Netlink {
 	/// The socket's file descriptor.
 	fd: c_int,
+	/// Tells whether the socket has been bound yet.
+	bound: Cell<bool>,
+	/// The sequence number to hand out to the next request, incremented on every call to
+	/// [`Netlink::next_seq`].
+	seq: Cell<u32>,
 }
 
 impl Netlink {
@@ -45,13 +69,76 @@ impl Netlink {
 
 		Ok(Self {
 			fd,
+			bound: Cell::new(false),
+			seq: Cell::new(1),
 		})
 	}
 
+	/// Returns a sequence number that has not been handed out before by this socket, for use on
+	/// a new request.
+	///
+	/// Requests must each use a fresh sequence number: [`NetlinkIter`] matches replies by `seq`
+	/// so that leftover multipart messages from an earlier, not-fully-drained request can't be
+	/// mistaken for belonging to a later one.
+	pub fn next_seq(&self) -> u32 {
+		let seq = self.seq.get();
+		self.seq.set(seq.wrapping_add(1));
+		seq
+	}
+
+	/// Binds the socket to the kernel, letting it pick our port ID, and joining no group.
+	fn bind(&self) -> io::Result<()> {
+		let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+		addr.nl_family = libc::AF_NETLINK as _;
+		addr.nl_pid = 0;
+		addr.nl_groups = 0;
+
+		let res = unsafe {
+			libc::bind(
+				self.fd,
+				&addr as *const _ as *const libc::sockaddr,
+				size_of::<libc::sockaddr_nl>() as _,
+			)
+		};
+		if res < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(())
+	}
+
+	/// Binds the socket on first use.
+	fn ensure_bound(&self) -> io::Result<()> {
+		if !self.bound.get() {
+			self.bind()?;
+			self.bound.set(true);
+		}
+
+		Ok(())
+	}
+
 	/// Low-level interface to send messages on the socket.
-	pub unsafe fn send_to(&self, _buf: &[u8]) -> io::Result<()> {
-		// TODO
-		todo!()
+	pub unsafe fn send_to(&self, buf: &[u8]) -> io::Result<()> {
+		self.ensure_bound()?;
+
+		let res = libc::sendto(self.fd, buf.as_ptr() as *const _, buf.len(), 0, null(), 0);
+		if res < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(())
+	}
+
+	/// Low-level interface to receive messages on the socket.
+	pub unsafe fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+		self.ensure_bound()?;
+
+		let res = libc::recv(self.fd, buf.as_mut_ptr() as *mut _, buf.len(), 0);
+		if res < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(res as usize)
 	}
 }
 
@@ -69,15 +156,126 @@ pub struct NetlinkIter<'sock, T> {
 	sock: &'sock Netlink,
 	/// The sequence number on which the iterator works.
 	seq: u32,
+	/// The current receive buffer, holding zero or more not-yet-read messages.
+	buf: Vec<u8>,
+	/// The offset of the next message to read in `buf`.
+	pos: usize,
+	/// Tells whether the response has ended: `NLMSG_DONE`, an error, a final ACK or a malformed
+	/// message has been received.
+	done: bool,
 
 	_phantom: PhantomData<T>,
 }
 
-impl<'sock, T> Iterator for NetlinkIter<'sock, T> {
+impl<'sock, T> NetlinkIter<'sock, T> {
+	/// Creates a new iterator reading the (potentially multipart) response to `seq` on `sock`.
+	pub(crate) fn new(sock: &'sock Netlink, seq: u32) -> Self {
+		Self {
+			sock,
+			seq,
+			buf: Vec::new(),
+			pos: 0,
+			done: false,
+			_phantom: PhantomData,
+		}
+	}
+
+	/// Issues a `recv` call, replacing the buffer with the newly received datagram.
+	fn fill(&mut self) -> io::Result<()> {
+		let mut buf = vec![0u8; 1 << 16];
+		let len = unsafe { self.sock.recv(&mut buf)? };
+		buf.truncate(len);
+
+		self.buf = buf;
+		self.pos = 0;
+
+		Ok(())
+	}
+}
+
+impl<'sock, T: NlPayload> Iterator for NetlinkIter<'sock, T> {
 	type Item = io::Result<T>;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		// TODO
-		todo!()
+		loop {
+			if self.done {
+				return None;
+			}
+
+			if self.pos >= self.buf.len() {
+				if let Err(e) = self.fill() {
+					return Some(Err(e));
+				}
+				if self.buf.is_empty() {
+					return None;
+				}
+			}
+
+			let remain = &self.buf[self.pos..];
+			if remain.len() < size_of::<NlMsgHdr>() {
+				self.done = true;
+				return Some(Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					"truncated netlink message header",
+				)));
+			}
+
+			let hdr = unsafe { &*(remain.as_ptr() as *const NlMsgHdr) };
+			let msg_len = hdr.nlmsg_len as usize;
+			if msg_len < size_of::<NlMsgHdr>() || msg_len > remain.len() {
+				self.done = true;
+				return Some(Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					"invalid netlink message length",
+				)));
+			}
+
+			let seq = hdr.nlmsg_seq;
+			let msg_type = hdr.nlmsg_type;
+			let multi = hdr.nlmsg_flags & libc::NLM_F_MULTI as u16 != 0;
+			let payload = &remain[size_of::<NlMsgHdr>()..msg_len];
+
+			// Build the error before advancing, since `payload` borrows `self.buf`
+			let error = (msg_type == libc::NLMSG_ERROR as u16).then(|| {
+				let errno = payload
+					.get(..size_of::<c_int>())
+					.map(|b| i32::from_ne_bytes(b.try_into().unwrap()))
+					.unwrap_or(0);
+				errno
+			});
+			let parsed = (!matches!(msg_type as _, libc::NLMSG_DONE | libc::NLMSG_ERROR))
+				.then(|| T::parse(payload));
+
+			self.pos += nlmsg_align(msg_len as u32) as usize;
+
+			// Messages belonging to another request are silently skipped
+			if seq != self.seq {
+				continue;
+			}
+
+			match msg_type as _ {
+				libc::NLMSG_DONE => {
+					self.done = true;
+					return None;
+				}
+
+				libc::NLMSG_ERROR => {
+					let errno = error.unwrap_or(0);
+					if errno == 0 {
+						// A plain ACK
+						if !multi {
+							self.done = true;
+							return None;
+						}
+						continue;
+					}
+
+					self.done = true;
+					return Some(Err(io::Error::from_raw_os_error(-errno)));
+				}
+
+				_ => return parsed,
+			}
+		}
 	}
-}
\ No newline at end of file
+}
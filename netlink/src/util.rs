@@ -0,0 +1,221 @@
+//! Parsing of netlink attributes (`rtattr`/`nlattr`), the TLV-encoded data that follows the
+//! fixed-size payload of many netlink messages.
+
+use std::ffi::CString;
+use std::io;
+use std::mem::size_of;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+
+/// Alignment applied to attributes.
+const RTA_ALIGNTO: usize = 4;
+
+/// Flag OR'd into `rta_type`/`nla_type` by `nla_nest_start()` to mark an attribute whose value
+/// is itself a sequence of attributes (e.g. `CTA_TUPLE_ORIG`, `IFLA_LINKINFO`).
+const NLA_F_NESTED: u16 = 0x8000;
+/// Flag OR'd into `rta_type`/`nla_type` to mark an attribute whose value is in network byte
+/// order.
+const NLA_F_NET_BYTEORDER: u16 = 0x4000;
+/// Mask isolating the actual attribute type, stripping the `NLA_F_*` flags above.
+const NLA_TYPE_MASK: u16 = !(NLA_F_NESTED | NLA_F_NET_BYTEORDER);
+
+/// Rounds `len` up to the next attribute alignment boundary.
+fn rta_align(len: usize) -> usize {
+	(len + RTA_ALIGNTO - 1) & !(RTA_ALIGNTO - 1)
+}
+
+/// Header in front of every attribute's value.
+#[repr(C)]
+struct RtAttr {
+	/// The length of the attribute, header included.
+	rta_len: u16,
+	/// The attribute's type.
+	rta_type: u16,
+}
+
+/// A single decoded attribute.
+struct Attr<'b> {
+	/// The attribute's type, as found in the kernel's `rtnetlink`/`netfilter` headers.
+	ty: u16,
+	/// The attribute's raw value.
+	value: &'b [u8],
+}
+
+/// An iterator over the attributes packed in a buffer.
+struct AttrIter<'b> {
+	/// The remaining, not yet read, attributes.
+	buf: &'b [u8],
+}
+
+impl<'b> AttrIter<'b> {
+	/// Creates an iterator over the attributes contained in `buf`.
+	fn new(buf: &'b [u8]) -> Self {
+		Self {
+			buf,
+		}
+	}
+}
+
+impl<'b> Iterator for AttrIter<'b> {
+	type Item = io::Result<Attr<'b>>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.buf.is_empty() {
+			return None;
+		}
+
+		if self.buf.len() < size_of::<RtAttr>() {
+			self.buf = &[];
+			return Some(Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"truncated netlink attribute header",
+			)));
+		}
+
+		let hdr = unsafe { &*(self.buf.as_ptr() as *const RtAttr) };
+		let len = hdr.rta_len as usize;
+		if len < size_of::<RtAttr>() || len > self.buf.len() {
+			self.buf = &[];
+			return Some(Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"invalid netlink attribute length",
+			)));
+		}
+
+		let attr = Attr {
+			// Strip the `NLA_F_NESTED`/`NLA_F_NET_BYTEORDER` flags the kernel OR's into the
+			// type of container/byte-order-sensitive attributes, so lookups by type keep
+			// matching regardless of whether the attribute happens to be nested.
+			ty: hdr.rta_type & NLA_TYPE_MASK,
+			value: &self.buf[size_of::<RtAttr>()..len],
+		};
+
+		let next = rta_align(len).min(self.buf.len());
+		self.buf = &self.buf[next..];
+
+		Some(Ok(attr))
+	}
+}
+
+/// A table giving indexed access to the attributes of a message, keyed by attribute type.
+///
+/// Attributes are collected once by [`AttrTbl::parse`], then looked up by type instead of
+/// re-scanning the buffer for every field of interest.
+pub struct AttrTbl<'b> {
+	/// The attributes found in the buffer, in order.
+	attrs: Vec<Attr<'b>>,
+}
+
+impl<'b> AttrTbl<'b> {
+	/// Parses every attribute in `buf`, building a lookup table.
+	///
+	/// This fails on the first malformed attribute, so a truncated or corrupted kernel message
+	/// cannot cause an out-of-bounds read down the line.
+	pub fn parse(buf: &'b [u8]) -> io::Result<Self> {
+		let attrs = AttrIter::new(buf).collect::<io::Result<Vec<_>>>()?;
+		Ok(Self {
+			attrs,
+		})
+	}
+
+	/// Returns the raw value of the first attribute of type `ty`, if present.
+	pub fn get_raw(&self, ty: u16) -> Option<&'b [u8]> {
+		self.attrs.iter().find(|a| a.ty == ty).map(|a| a.value)
+	}
+
+	/// Returns the attribute of type `ty`, decoded as a native-endian `u32`.
+	pub fn get_u32(&self, ty: u16) -> Option<u32> {
+		let value = self.get_raw(ty)?;
+		Some(u32::from_ne_bytes(value.try_into().ok()?))
+	}
+
+	/// Returns the attribute of type `ty`, decoded as a big-endian `u64` (the encoding used by
+	/// e.g. `CTA_COUNTERS_PACKETS`/`CTA_COUNTERS_BYTES`).
+	pub fn get_u64_be(&self, ty: u16) -> Option<u64> {
+		let value = self.get_raw(ty)?;
+		Some(u64::from_be_bytes(value.try_into().ok()?))
+	}
+
+	/// Returns the attribute of type `ty`, decoded as a nul-terminated string.
+	pub fn get_cstring(&self, ty: u16) -> Option<CString> {
+		let value = self.get_raw(ty)?;
+		let value = value.split(|b| *b == 0).next()?;
+		CString::new(value).ok()
+	}
+
+	/// Returns the attribute of type `ty`, decoded as an IPv4 address.
+	pub fn get_ipv4(&self, ty: u16) -> Option<IpAddr> {
+		let value = self.get_raw(ty)?;
+		let octets: [u8; 4] = value.try_into().ok()?;
+		Some(IpAddr::V4(Ipv4Addr::from(octets)))
+	}
+
+	/// Returns the attribute of type `ty`, decoded as an IPv6 address.
+	pub fn get_ipv6(&self, ty: u16) -> Option<IpAddr> {
+		let value = self.get_raw(ty)?;
+		let octets: [u8; 16] = value.try_into().ok()?;
+		Some(IpAddr::V6(Ipv6Addr::from(octets)))
+	}
+
+	/// Returns the attribute of type `ty`, treating its value as a nested sequence of
+	/// attributes (e.g. `IFLA_LINKINFO`), and builds a table over it.
+	pub fn get_nested(&self, ty: u16) -> Option<io::Result<AttrTbl<'b>>> {
+		let value = self.get_raw(ty)?;
+		Some(Self::parse(value))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// Appends a single `rtattr`-encoded attribute to `buf`.
+	fn push_attr(buf: &mut Vec<u8>, ty: u16, value: &[u8]) {
+		let len = (4 + value.len()) as u16;
+		buf.extend_from_slice(&len.to_ne_bytes());
+		buf.extend_from_slice(&ty.to_ne_bytes());
+		buf.extend_from_slice(value);
+		while buf.len() % 4 != 0 {
+			buf.push(0);
+		}
+	}
+
+	#[test]
+	fn parse_well_formed() {
+		let mut buf = Vec::new();
+		push_attr(&mut buf, 1, &42u32.to_ne_bytes());
+		push_attr(&mut buf, 2, b"hello\0");
+
+		let attrs = AttrTbl::parse(&buf).unwrap();
+		assert_eq!(attrs.get_u32(1), Some(42));
+		assert_eq!(attrs.get_cstring(2).unwrap().to_str().unwrap(), "hello");
+	}
+
+	#[test]
+	fn truncated_header() {
+		// A single byte cannot even hold `rta_len`/`rta_type`.
+		let buf = [0u8];
+		assert!(AttrTbl::parse(&buf).is_err());
+	}
+
+	#[test]
+	fn rta_len_exceeds_buffer() {
+		let mut buf = Vec::new();
+		push_attr(&mut buf, 1, &42u32.to_ne_bytes());
+		// Claim a length far larger than what actually follows the header.
+		buf[0..2].copy_from_slice(&0xffffu16.to_ne_bytes());
+
+		assert!(AttrTbl::parse(&buf).is_err());
+	}
+
+	#[test]
+	fn nested_on_corrupt_blob() {
+		let mut buf = Vec::new();
+		// The nested value is a single truncated byte, not a valid attribute stream.
+		push_attr(&mut buf, NLA_F_NESTED | 1, &[0]);
+
+		let attrs = AttrTbl::parse(&buf).unwrap();
+		assert!(attrs.get_nested(1).unwrap().is_err());
+	}
+}
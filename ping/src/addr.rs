@@ -0,0 +1,19 @@
+//! Resolution of the ping destination into an IP address.
+
+use std::io;
+use std::net::IpAddr;
+use std::net::ToSocketAddrs;
+
+/// Parses `dest`, which may be a literal IP address or a hostname, resolving it to an
+/// [`IpAddr`].
+pub fn parse(dest: &str) -> io::Result<IpAddr> {
+	if let Ok(addr) = dest.parse::<IpAddr>() {
+		return Ok(addr);
+	}
+
+	(dest, 0)
+		.to_socket_addrs()?
+		.next()
+		.map(|s| s.ip())
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "could not resolve host"))
+}
@@ -0,0 +1,114 @@
+//! Building and parsing of ICMP echo request/reply packets.
+
+use crate::sock::IcmpSocket;
+use std::io;
+use std::mem::size_of;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// ICMP type: echo request.
+const ICMP_ECHO: u8 = 8;
+/// ICMP type: echo reply.
+const ICMP_ECHOREPLY: u8 = 0;
+
+/// The size in bytes of the monotonic timestamp stamped at the start of the echo payload.
+const TIMESTAMP_SIZE: usize = size_of::<u64>();
+
+/// Returns the current monotonic time, in nanoseconds.
+///
+/// This is stamped into outgoing packets and compared against on the matching reply, so the
+/// measured RTT reflects the real time spent in flight instead of an assumption about send
+/// timing.
+fn monotonic_now() -> u64 {
+	let mut ts = libc::timespec {
+		tv_sec: 0,
+		tv_nsec: 0,
+	};
+	unsafe {
+		libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+	}
+
+	ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+/// A decoded echo reply.
+pub struct Packet {
+	/// The packet's sequence number.
+	pub seq: u16,
+	/// The size of the payload, in bytes.
+	pub payload_size: usize,
+	/// The round-trip time measured from the timestamp embedded in the payload.
+	///
+	/// `None` if the payload was too small to carry a timestamp (a truncated or foreign
+	/// packet), in which case the reply must not be accounted for in RTT statistics.
+	pub rtt: Option<Duration>,
+}
+
+/// Writes an echo request of `size` bytes (ICMP header included) to `sock`, addressed to `addr`.
+///
+/// The current monotonic time is stamped at the start of the payload, so the RTT of the matching
+/// reply can be measured precisely regardless of send/receive timing drift.
+pub fn write_ping(sock: &mut IcmpSocket, addr: &IpAddr, seq: u16, size: usize) -> io::Result<()> {
+	let mut buf = vec![0u8; size];
+
+	buf[0] = ICMP_ECHO;
+	buf[1] = 0; // code
+	buf[4..6].copy_from_slice(&0u16.to_be_bytes()); // identifier
+	buf[6..8].copy_from_slice(&seq.to_be_bytes());
+
+	let payload = &mut buf[8..];
+	if payload.len() >= TIMESTAMP_SIZE {
+		payload[..TIMESTAMP_SIZE].copy_from_slice(&monotonic_now().to_ne_bytes());
+	}
+
+	// ICMPv6's checksum (RFC 4443) covers a pseudo-header (addresses, length, next-header) that
+	// is only known to the kernel at send time, so it is left zero here and computed by the
+	// kernel instead, via the `IPV6_CHECKSUM` socket option set up in `IcmpSocket::new`. ICMPv4
+	// has no such pseudo-header and can be checksummed directly.
+	if addr.is_ipv4() {
+		let checksum = checksum(&buf);
+		buf[2..4].copy_from_slice(&checksum.to_be_bytes());
+	}
+
+	sock.send_to(&buf, addr)
+}
+
+/// Parses a received buffer as an echo reply, returning `None` if it is not one.
+pub fn parse(buf: &[u8]) -> Option<Packet> {
+	if buf.len() < 8 || buf[0] != ICMP_ECHOREPLY {
+		return None;
+	}
+
+	let seq = u16::from_be_bytes(buf[6..8].try_into().ok()?);
+	let payload = &buf[8..];
+
+	let rtt = (payload.len() >= TIMESTAMP_SIZE).then(|| {
+		let sent = u64::from_ne_bytes(payload[..TIMESTAMP_SIZE].try_into().unwrap());
+		Duration::from_nanos(monotonic_now().saturating_sub(sent))
+	});
+
+	Some(Packet {
+		seq,
+		payload_size: payload.len(),
+		rtt,
+	})
+}
+
+/// Computes the internet checksum (RFC 1071) of `data`.
+fn checksum(data: &[u8]) -> u16 {
+	let mut sum = 0u32;
+
+	let mut chunks = data.chunks_exact(2);
+	for chunk in &mut chunks {
+		sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+	}
+	if let Some(&last) = chunks.remainder().first() {
+		sum += (last as u32) << 8;
+	}
+
+	while sum >> 16 != 0 {
+		sum = (sum & 0xffff) + (sum >> 16);
+	}
+
+	!(sum as u16)
+}
@@ -2,34 +2,19 @@
 
 use crate::addr;
 use crate::packet;
+use crate::signal::SigIntFd;
 use crate::sock::IcmpSocket;
-use crate::timer::Timer;
+use crate::timer::TimerFd;
 use std::cmp::max;
 use std::cmp::min;
+use std::collections::HashSet;
 use std::io;
-use std::io::ErrorKind;
 use std::net::IpAddr;
 use std::num::NonZeroU16;
 use std::process::exit;
-use std::ptr::null_mut;
-use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering;
 use std::time::Duration;
 use std::time::Instant;
 
-/// Atomic bool telling whether a `SIGALRM` signal has been received.
-static ALARM: AtomicBool = AtomicBool::new(false);
-/// Atomic bool telling whether a `SIGINT` signal has been received.
-static INT: AtomicBool = AtomicBool::new(false);
-
-extern "C" fn alarm_handler() {
-	ALARM.store(true, Ordering::Relaxed);
-}
-
-extern "C" fn int_handler() {
-	INT.store(true, Ordering::Relaxed);
-}
-
 /// A pinging context.
 pub struct PingContext {
 	/// The number of packets to receive.
@@ -74,37 +59,42 @@ impl PingContext {
 	pub fn ping(&mut self) -> io::Result<()> {
 		let addr = addr::parse(&self.dest)?;
 
-		// Catch signals
-		unsafe {
-			libc::sigaction(
-				libc::SIGALRM,
-				&libc::sigaction {
-					sa_sigaction: alarm_handler as _,
-					sa_mask: std::mem::transmute::<_, _>([0u32; 32]),
-					sa_flags: 0,
-					sa_restorer: None,
-				},
-				null_mut::<_>(),
-			);
-			libc::sigaction(
-				libc::SIGINT,
-				&libc::sigaction {
-					sa_sigaction: int_handler as _,
-					sa_mask: std::mem::transmute::<_, _>([0u32; 32]),
-					sa_flags: 0,
-					sa_restorer: None,
-				},
-				null_mut::<_>(),
-			);
+		// Event sources: interval pacing, an optional deadline, `SIGINT`, and the socket itself
+		let interval_timer = TimerFd::interval(self.interval)?;
+		let deadline_timer = self.deadline.map(TimerFd::oneshot).transpose()?;
+		let sigint = SigIntFd::new()?;
+
+		let mut fds = vec![
+			libc::pollfd {
+				fd: interval_timer.as_raw_fd(),
+				events: libc::POLLIN,
+				revents: 0,
+			},
+			libc::pollfd {
+				fd: sigint.as_raw_fd(),
+				events: libc::POLLIN,
+				revents: 0,
+			},
+			libc::pollfd {
+				fd: self.sock.as_raw_fd(),
+				events: libc::POLLIN,
+				revents: 0,
+			},
+		];
+		if let Some(deadline_timer) = &deadline_timer {
+			fds.push(libc::pollfd {
+				fd: deadline_timer.as_raw_fd(),
+				events: libc::POLLIN,
+				revents: 0,
+			});
 		}
 
-		// Timing
-		let _timer = Timer::new(self.interval);
 		let start = Instant::now();
 
 		// Stats
 		let mut transmit_count: u16 = 0;
 		let mut receive_count: u16 = 0;
+		let mut duplicate_count: u16 = 0;
 		// The minimum reply delay
 		let mut min_delta = u128::MAX;
 		// The maximum reply delay
@@ -114,6 +104,11 @@ impl PingContext {
 		// The sum of squared reply delays
 		let mut sum_squared_delta = 0;
 
+		// The sequence numbers that have already been answered, to detect duplicate replies
+		let mut seen_seqs = HashSet::new();
+		// The sequence number of the last reply accepted as new, to detect out-of-order replies
+		let mut last_accepted_seq: Option<u16> = None;
+
 		// Send first packet
 		let res = self.send_packet(&addr, transmit_count);
 		match res {
@@ -133,45 +128,107 @@ impl PingContext {
 			self.dest, addr, self.packet_size
 		);
 
-		loop {
+		'main: loop {
 			// Break if count has been reached
 			let cont = self.count.map(|c| receive_count < c.get()).unwrap_or(true);
-			if INT.load(Ordering::Relaxed) || !cont {
+			if !cont {
 				break;
 			}
 
-			// Send signal if interval has been reached
-			if ALARM.load(Ordering::Relaxed) {
-				// Reset timer
-				ALARM.store(false, Ordering::Relaxed);
+			for fd in &mut fds {
+				fd.revents = 0;
+			}
+
+			let res = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, -1) };
+			if res < 0 {
+				let e = io::Error::last_os_error();
+				if e.kind() == io::ErrorKind::Interrupted {
+					continue;
+				}
+				return Err(e);
+			}
+
+			// Interval timer: send the next echo
+			if fds[0].revents & libc::POLLIN != 0 {
+				interval_timer.consume()?;
 
-				self.send_packet(&addr, transmit_count)?;
-				transmit_count += 1;
+				let cont = self.count.map(|c| transmit_count < c.get()).unwrap_or(true);
+				if cont {
+					self.send_packet(&addr, transmit_count)?;
+					transmit_count += 1;
+				}
 			}
 
-			let res = self.sock.recvmsg(&mut buf, &addr);
-			let (len, info) = match res {
-				Ok(r) => r,
-				// If the timer expired or if pinging has been interrupted
-				Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-				Err(e) => return Err(e),
-			};
-
-			// Check packet
-			if let Some(pack) = packet::parse(&buf[..len]) {
-				let transmit_ts = start + self.interval * pack.seq as _;
-				let delta = Instant::now().duration_since(transmit_ts).as_millis();
-
-				println!(
-					"{} bytes from {}: icmp_seq={} ttl={} time={} ms",
-					pack.payload_size, info.src_addr, pack.seq, info.ttl, delta
-				);
-
-				receive_count += 1;
-				min_delta = min(min_delta, delta);
-				max_delta = max(max_delta, delta);
-				sum_delta += delta;
-				sum_squared_delta += delta * delta;
+			// SIGINT: stop cleanly instead of depending on EINTR
+			if fds[1].revents & libc::POLLIN != 0 {
+				sigint.consume()?;
+				break 'main;
+			}
+
+			// Socket readable: receive and parse one reply
+			if fds[2].revents & libc::POLLIN != 0 {
+				let (len, info) = self.sock.recvmsg(&mut buf, &addr)?;
+
+				if let Some(pack) = packet::parse(&buf[..len]) {
+					// A reply for a sequence number we have already answered is a duplicate
+					let is_dup = !seen_seqs.insert(pack.seq);
+					// A reply answering a lower sequence number than the last one we accepted
+					// arrived out of order
+					let out_of_order =
+						!is_dup && last_accepted_seq.is_some_and(|last| pack.seq < last);
+
+					if is_dup {
+						duplicate_count += 1;
+					} else {
+						receive_count += 1;
+						last_accepted_seq = Some(pack.seq);
+					}
+
+					let marker = if is_dup {
+						" (DUP!)"
+					} else if out_of_order {
+						" (out of order)"
+					} else {
+						""
+					};
+
+					match pack.rtt {
+						// The reply carried a valid embedded timestamp: account for it
+						Some(rtt) => {
+							let delta = rtt.as_millis();
+
+							println!(
+								"{} bytes from {}: icmp_seq={} ttl={} time={} ms{}",
+								pack.payload_size, info.src_addr, pack.seq, info.ttl, delta, marker
+							);
+
+							// Duplicates must not skew the RTT statistics
+							if !is_dup {
+								min_delta = min(min_delta, delta);
+								max_delta = max(max_delta, delta);
+								sum_delta += delta;
+								sum_squared_delta += delta * delta;
+							}
+						}
+
+						// The payload was too small to carry a timestamp: still report the
+						// reply, but leave it out of the min/max and delay sums
+						None => {
+							println!(
+								"{} bytes from {}: icmp_seq={} ttl={}{}",
+								pack.payload_size, info.src_addr, pack.seq, info.ttl, marker
+							);
+						}
+					}
+				}
+			}
+
+			// Deadline timer: stop regardless of how many packets have been sent/received
+			if let Some(deadline_timer) = &deadline_timer {
+				if fds[3].revents & libc::POLLIN != 0 {
+					deadline_timer.consume()?;
+					break 'main;
+				}
 			}
 		}
 
@@ -186,10 +243,12 @@ impl PingContext {
 
 		println!();
 		println!("--- {} ping statistics ---", self.dest);
+		print!("{} packets transmitted, {} received, ", transmit_count, receive_count);
+		if duplicate_count > 0 {
+			print!("+{} duplicates, ", duplicate_count);
+		}
 		println!(
-			"{} packets transmitted, {} received, {}% packet loss, time {} ms",
-			transmit_count,
-			receive_count,
+			"{}% packet loss, time {} ms",
 			loss_percentage,
 			elapsed.as_millis()
 		);
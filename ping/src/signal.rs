@@ -0,0 +1,75 @@
+//! A `signalfd`-based way to observe `SIGINT` as a pollable file descriptor, so Ctrl-C can
+//! unblock the event loop deterministically instead of relying on `EINTR` racing a handler.
+
+use std::io;
+use std::mem::size_of;
+use std::mem::MaybeUninit;
+use std::os::fd::RawFd;
+use std::ptr::null_mut;
+
+/// A file descriptor that becomes readable when `SIGINT` is pending.
+pub struct SigIntFd {
+	/// The signal's file descriptor.
+	fd: RawFd,
+}
+
+impl SigIntFd {
+	/// Creates a new instance, blocking `SIGINT`'s default disposition so that it is only ever
+	/// observed through this file descriptor.
+	pub fn new() -> io::Result<Self> {
+		unsafe {
+			let mut mask: libc::sigset_t = MaybeUninit::zeroed().assume_init();
+			libc::sigemptyset(&mut mask);
+			libc::sigaddset(&mut mask, libc::SIGINT);
+
+			if libc::sigprocmask(libc::SIG_BLOCK, &mask, null_mut()) < 0 {
+				return Err(io::Error::last_os_error());
+			}
+
+			let fd = libc::signalfd(-1, &mask, libc::SFD_NONBLOCK);
+			if fd < 0 {
+				return Err(io::Error::last_os_error());
+			}
+
+			Ok(Self {
+				fd,
+			})
+		}
+	}
+
+	/// Returns the raw file descriptor backing this signal, for use with `poll`.
+	pub fn as_raw_fd(&self) -> RawFd {
+		self.fd
+	}
+
+	/// Consumes one pending `SIGINT` notification.
+	pub fn consume(&self) -> io::Result<()> {
+		let mut info: MaybeUninit<libc::signalfd_siginfo> = MaybeUninit::uninit();
+
+		let res = unsafe {
+			libc::read(
+				self.fd,
+				info.as_mut_ptr() as *mut _,
+				size_of::<libc::signalfd_siginfo>(),
+			)
+		};
+		if res < 0 {
+			let e = io::Error::last_os_error();
+			if e.kind() == io::ErrorKind::WouldBlock {
+				return Ok(());
+			}
+
+			return Err(e);
+		}
+
+		Ok(())
+	}
+}
+
+impl Drop for SigIntFd {
+	fn drop(&mut self) {
+		unsafe {
+			libc::close(self.fd);
+		}
+	}
+}
@@ -0,0 +1,232 @@
+//! ICMP raw socket wrapper, handling per-packet send/receive along with ancillary information
+//! (TTL, source address).
+
+use std::ffi::c_int;
+use std::io;
+use std::mem::size_of;
+use std::mem::MaybeUninit;
+use std::net::IpAddr;
+
+/// Information carried alongside a received packet.
+pub struct Info {
+	/// The address the packet was received from.
+	pub src_addr: IpAddr,
+	/// The IP TTL the packet was received with.
+	pub ttl: u8,
+}
+
+/// A raw ICMP socket.
+pub struct IcmpSocket {
+	/// The socket's file descriptor.
+	fd: i32,
+}
+
+impl IcmpSocket {
+	/// Creates a new socket able to ping `addr`.
+	pub fn new(addr: &IpAddr) -> io::Result<Self> {
+		let (family, proto) = match addr {
+			IpAddr::V4(_) => (libc::AF_INET, libc::IPPROTO_ICMP),
+			IpAddr::V6(_) => (libc::AF_INET6, libc::IPPROTO_ICMPV6),
+		};
+
+		let fd = unsafe { libc::socket(family, libc::SOCK_RAW, proto) };
+		if fd < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		// Have the kernel hand us the hop limit of received packets as ancillary data, so
+		// `recvmsg` can report the real TTL instead of a fabricated one
+		let enable: c_int = 1;
+		let (level, opt) = match addr {
+			IpAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_RECVTTL),
+			IpAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_RECVHOPLIMIT),
+		};
+		let res = unsafe {
+			libc::setsockopt(
+				fd,
+				level,
+				opt,
+				&enable as *const _ as *const _,
+				size_of::<c_int>() as _,
+			)
+		};
+		if res < 0 {
+			let e = io::Error::last_os_error();
+			unsafe {
+				libc::close(fd);
+			}
+			return Err(e);
+		}
+
+		if addr.is_ipv6() {
+			// Unlike ICMPv4, ICMPv6's checksum (RFC 4443) covers a pseudo-header built from the
+			// source/destination addresses, so it cannot be computed until the kernel routes the
+			// packet. `IPV6_CHECKSUM` has the kernel compute and patch it in at the given byte
+			// offset into the payload, matching the 2-byte-in checksum field left zeroed by
+			// `packet::write_ping`.
+			let offset: c_int = 2;
+			let res = unsafe {
+				libc::setsockopt(
+					fd,
+					libc::IPPROTO_IPV6,
+					libc::IPV6_CHECKSUM,
+					&offset as *const _ as *const _,
+					size_of::<c_int>() as _,
+				)
+			};
+			if res < 0 {
+				let e = io::Error::last_os_error();
+				unsafe {
+					libc::close(fd);
+				}
+				return Err(e);
+			}
+		}
+
+		Ok(Self {
+			fd,
+		})
+	}
+
+	/// Returns the raw file descriptor backing this socket.
+	pub fn as_raw_fd(&self) -> i32 {
+		self.fd
+	}
+
+	/// Sends `buf` to `addr`.
+	pub fn send_to(&mut self, buf: &[u8], addr: &IpAddr) -> io::Result<()> {
+		let res = match addr {
+			IpAddr::V4(v4) => {
+				let sockaddr = libc::sockaddr_in {
+					sin_family: libc::AF_INET as _,
+					sin_port: 0,
+					sin_addr: libc::in_addr {
+						s_addr: u32::from_ne_bytes(v4.octets()),
+					},
+					sin_zero: [0; 8],
+				};
+
+				unsafe {
+					libc::sendto(
+						self.fd,
+						buf.as_ptr() as *const _,
+						buf.len(),
+						0,
+						&sockaddr as *const _ as *const libc::sockaddr,
+						size_of::<libc::sockaddr_in>() as _,
+					)
+				}
+			}
+
+			IpAddr::V6(v6) => {
+				let sockaddr = libc::sockaddr_in6 {
+					sin6_family: libc::AF_INET6 as _,
+					sin6_port: 0,
+					sin6_flowinfo: 0,
+					sin6_addr: libc::in6_addr {
+						s6_addr: v6.octets(),
+					},
+					sin6_scope_id: 0,
+				};
+
+				unsafe {
+					libc::sendto(
+						self.fd,
+						buf.as_ptr() as *const _,
+						buf.len(),
+						0,
+						&sockaddr as *const _ as *const libc::sockaddr,
+						size_of::<libc::sockaddr_in6>() as _,
+					)
+				}
+			}
+		};
+
+		if res < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(())
+	}
+
+	/// Receives a packet into `buf`, returning its length along with information about where it
+	/// came from.
+	pub fn recvmsg(&mut self, buf: &mut [u8], _addr: &IpAddr) -> io::Result<(usize, Info)> {
+		let mut src: MaybeUninit<libc::sockaddr_storage> = MaybeUninit::zeroed();
+		let mut iov = libc::iovec {
+			iov_base: buf.as_mut_ptr() as *mut _,
+			iov_len: buf.len(),
+		};
+		// Large enough for an `IP_TTL`/`IPV6_HOPLIMIT` cmsg, with room to spare
+		let mut control = [0u8; 64];
+
+		let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+		msg.msg_name = src.as_mut_ptr() as *mut _;
+		msg.msg_namelen = size_of::<libc::sockaddr_storage>() as _;
+		msg.msg_iov = &mut iov;
+		msg.msg_iovlen = 1;
+		msg.msg_control = control.as_mut_ptr() as *mut _;
+		msg.msg_controllen = control.len() as _;
+
+		let res = unsafe { libc::recvmsg(self.fd, &mut msg, 0) };
+		if res < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		let src = unsafe { src.assume_init() };
+		let src_addr = sockaddr_to_ip(&src);
+		let ttl = unsafe { extract_ttl(&msg) };
+
+		Ok((
+			res as usize,
+			Info {
+				src_addr,
+				ttl,
+			},
+		))
+	}
+}
+
+/// Converts a raw `sockaddr_storage` into an [`IpAddr`].
+fn sockaddr_to_ip(addr: &libc::sockaddr_storage) -> IpAddr {
+	match addr.ss_family as i32 {
+		libc::AF_INET => {
+			let addr = unsafe { &*(addr as *const _ as *const libc::sockaddr_in) };
+			IpAddr::from(addr.sin_addr.s_addr.to_ne_bytes())
+		}
+
+		_ => {
+			let addr = unsafe { &*(addr as *const _ as *const libc::sockaddr_in6) };
+			IpAddr::from(addr.sin6_addr.s6_addr)
+		}
+	}
+}
+
+/// Walks the ancillary data of `msg` looking for the `IP_TTL`/`IPV6_HOPLIMIT` cmsg enabled by
+/// [`IcmpSocket::new`], returning `0` if it is absent.
+unsafe fn extract_ttl(msg: &libc::msghdr) -> u8 {
+	let mut cmsg = libc::CMSG_FIRSTHDR(msg);
+
+	while !cmsg.is_null() {
+		let hdr = &*cmsg;
+		let is_ttl = (hdr.cmsg_level == libc::IPPROTO_IP && hdr.cmsg_type == libc::IP_TTL)
+			|| (hdr.cmsg_level == libc::IPPROTO_IPV6 && hdr.cmsg_type == libc::IPV6_HOPLIMIT);
+
+		if is_ttl {
+			let data = libc::CMSG_DATA(cmsg) as *const c_int;
+			return (*data) as u8;
+		}
+
+		cmsg = libc::CMSG_NXTHDR(msg, cmsg);
+	}
+
+	0
+}
+
+impl Drop for IcmpSocket {
+	fn drop(&mut self) {
+		unsafe {
+			libc::close(self.fd);
+		}
+	}
+}
@@ -0,0 +1,94 @@
+//! `timerfd`-backed timers used to pace outgoing echo requests and enforce a deadline.
+
+use std::io;
+use std::os::fd::RawFd;
+use std::ptr::null_mut;
+use std::time::Duration;
+
+/// A `timerfd`-backed timer, pollable like any other file descriptor.
+pub struct TimerFd {
+	/// The timer's file descriptor.
+	fd: RawFd,
+}
+
+impl TimerFd {
+	/// Creates a new, disarmed timer.
+	fn create() -> io::Result<Self> {
+		let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, libc::TFD_NONBLOCK) };
+		if fd < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(Self {
+			fd,
+		})
+	}
+
+	/// Creates a timer that fires every `interval`, starting after one `interval`.
+	pub fn interval(interval: Duration) -> io::Result<Self> {
+		let timer = Self::create()?;
+		timer.arm(interval, interval)?;
+		Ok(timer)
+	}
+
+	/// Creates a timer that fires once, after `delay`.
+	pub fn oneshot(delay: Duration) -> io::Result<Self> {
+		let timer = Self::create()?;
+		timer.arm(delay, Duration::ZERO)?;
+		Ok(timer)
+	}
+
+	/// Arms the timer to first fire after `value`, then every `interval` (`Duration::ZERO` for
+	/// a one-shot timer).
+	fn arm(&self, value: Duration, interval: Duration) -> io::Result<()> {
+		let spec = libc::itimerspec {
+			it_interval: duration_to_timespec(interval),
+			it_value: duration_to_timespec(value),
+		};
+
+		let res = unsafe { libc::timerfd_settime(self.fd, 0, &spec, null_mut()) };
+		if res < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(())
+	}
+
+	/// Returns the raw file descriptor backing this timer, for use with `poll`.
+	pub fn as_raw_fd(&self) -> RawFd {
+		self.fd
+	}
+
+	/// Consumes the expiration counter, clearing the timer's readable state until it next fires.
+	pub fn consume(&self) -> io::Result<()> {
+		let mut buf = [0u8; 8];
+
+		let res = unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut _, buf.len()) };
+		if res < 0 {
+			let e = io::Error::last_os_error();
+			if e.kind() == io::ErrorKind::WouldBlock {
+				return Ok(());
+			}
+
+			return Err(e);
+		}
+
+		Ok(())
+	}
+}
+
+impl Drop for TimerFd {
+	fn drop(&mut self) {
+		unsafe {
+			libc::close(self.fd);
+		}
+	}
+}
+
+/// Converts a [`Duration`] to a `timespec`.
+fn duration_to_timespec(d: Duration) -> libc::timespec {
+	libc::timespec {
+		tv_sec: d.as_secs() as _,
+		tv_nsec: d.subsec_nanos() as _,
+	}
+}